@@ -1,10 +1,14 @@
 use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
 
+#[derive(Clone)]
 struct Grid {
     cells: [u8; 9 * 9],
 
     lines_state: [u16; 9],
     columns_state: [u16; 9],
+    boxes_state: [u16; 9],
 
     solved_indices_stack: Vec<usize>,
 }
@@ -15,6 +19,36 @@ enum PotentialState {
     None,
 }
 
+#[derive(Debug, Default)]
+struct SolveStats {
+    naked_singles: usize,
+    guesses: usize,
+    max_depth: usize,
+}
+
+#[derive(Debug)]
+enum ParseError {
+    InvalidLength(usize),
+    InvalidDigit(char),
+    OutOfRangeValue(u8),
+    DuplicateCoordinate(usize, usize),
+    ConflictingGiven(usize, usize),
+    InvalidFormat(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidLength(length) => write!(f, "expected 81 characters, got {}", length),
+            ParseError::InvalidDigit(character) => write!(f, "'{}' is not a valid digit (expected 1-9, 0 or .)", character),
+            ParseError::OutOfRangeValue(value) => write!(f, "{} is not a valid value (expected 1-9)", value),
+            ParseError::DuplicateCoordinate(line, column) => write!(f, "cell ({}, {}) was given a value twice", line, column),
+            ParseError::ConflictingGiven(line, column) => write!(f, "cell ({}, {}) conflicts with another given in its row, column or box", line, column),
+            ParseError::InvalidFormat(reason) => write!(f, "invalid puzzle format: {}", reason),
+        }
+    }
+}
+
 impl Grid {
     fn create(values: &[(u8, (usize, usize))]) -> Grid {
         let mut cells = [0; 9 * 9];
@@ -28,6 +62,7 @@ impl Grid {
             cells,
             lines_state: [0; 9],
             columns_state: [0; 9],
+            boxes_state: [0; 9],
             solved_indices_stack: Vec::new(),
         }
     }
@@ -40,36 +75,119 @@ impl Grid {
         return (index / 9, index % 9);
     }
 
-    fn interpret_potential(potential: u16) -> PotentialState {
-        let mut count = 0;
-        let mut last_value = 0;
-        for i in 1..10 {
-            if potential & (1 << i) != 0 {
-                count += 1;
-                last_value = i;
+    fn box_index_of(line: usize, column: usize) -> usize {
+        return (line / 3) * 3 + column / 3;
+    }
+
+    fn validate_givens(&self) -> Result<(), ParseError> {
+        let mut lines_seen = [0u16; 9];
+        let mut columns_seen = [0u16; 9];
+        let mut boxes_seen = [0u16; 9];
+
+        for (index, value) in self.cells.iter().enumerate() {
+            if *value == 0 {
+                continue;
             }
-        }
 
-        match count {
-            0 => { PotentialState::None },
-            1 => { PotentialState::One(last_value) },
-            _ => { PotentialState::Several(count) },
+            let (line, column) = Grid::coordinates_of(index);
+            let box_index = Grid::box_index_of(line, column);
+            let value_bit = 1u16 << value;
+
+            if lines_seen[line] & value_bit != 0
+                || columns_seen[column] & value_bit != 0
+                || boxes_seen[box_index] & value_bit != 0
+            {
+                return Err(ParseError::ConflictingGiven(line, column));
+            }
+
+            lines_seen[line] |= value_bit;
+            columns_seen[column] |= value_bit;
+            boxes_seen[box_index] |= value_bit;
         }
+
+        return Ok(());
     }
 
-    fn get_potential_value_at(potential: u16, index: u8) -> u8 {
-        let mut current_index = 0;
-        for i in 1..10 {
-            if potential & (1 << i) != 0 {
-                if current_index == index {
-                    return i;
-                }
+    fn from_reader<R: BufRead>(reader: R) -> Result<Grid, ParseError> {
+        let mut lines = reader.lines();
+
+        let header = lines.next()
+            .ok_or_else(|| ParseError::InvalidFormat("missing \"9,9\" header".to_string()))?
+            .map_err(|error| ParseError::InvalidFormat(error.to_string()))?;
 
-                current_index += 1;
+        if header.trim() != "9,9" {
+            return Err(ParseError::InvalidFormat(format!("expected \"9,9\" header, got \"{}\"", header)));
+        }
+
+        let mut values: Vec<(u8, (usize, usize))> = Vec::new();
+        let mut seen = [false; 9 * 9];
+
+        for line in lines {
+            let line = line.map_err(|error| ParseError::InvalidFormat(error.to_string()))?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split(',').collect();
+            if parts.len() != 3 {
+                return Err(ParseError::InvalidFormat(format!("expected \"row,col,value\", got \"{}\"", line)));
             }
+
+            let row = parts[0].trim().parse::<usize>()
+                .map_err(|_| ParseError::InvalidFormat(format!("bad row in \"{}\"", line)))?;
+            let column = parts[1].trim().parse::<usize>()
+                .map_err(|_| ParseError::InvalidFormat(format!("bad column in \"{}\"", line)))?;
+            let value = parts[2].trim().parse::<u8>()
+                .map_err(|_| ParseError::InvalidFormat(format!("bad value in \"{}\"", line)))?;
+
+            if row >= 9 || column >= 9 {
+                return Err(ParseError::InvalidFormat(format!("coordinate out of range in \"{}\"", line)));
+            }
+
+            if value == 0 || value > 9 {
+                return Err(ParseError::OutOfRangeValue(value));
+            }
+
+            let index = Grid::index_of(row, column);
+            if seen[index] {
+                return Err(ParseError::DuplicateCoordinate(row, column));
+            }
+            seen[index] = true;
+
+            values.push((value, (row, column)));
         }
 
-        return 0;
+        let grid = Grid::create(&values);
+        grid.validate_givens()?;
+
+        return Ok(grid);
+    }
+
+    fn interpret_potential(potential: u16) -> PotentialState {
+        if potential == 0 {
+            return PotentialState::None;
+        }
+
+        if potential.is_power_of_two() {
+            return PotentialState::One(potential.trailing_zeros() as u8);
+        }
+
+        return PotentialState::Several(potential.count_ones() as u8);
+    }
+
+    fn get_potential_value_at(potential: u16, index: u8) -> u8 {
+        let mut mask = potential;
+        for _ in 0..index {
+            mask &= mask - 1;
+        }
+
+        return mask.trailing_zeros() as u8;
+    }
+
+    fn solution_rate(&self) -> f64 {
+        let solved_count = self.cells.iter().filter(|cell| **cell > 0).count();
+        return solved_count as f64 / (9 * 9) as f64;
     }
 
     fn any_unsolved_cell(&self) -> bool {
@@ -92,7 +210,8 @@ impl Grid {
             }
 
             let (line, column) = Grid::coordinates_of(index);
-            let potential = self.lines_state[line] & self.columns_state[column];
+            let box_index = Grid::box_index_of(line, column);
+            let potential = self.lines_state[line] & self.columns_state[column] & self.boxes_state[box_index];
             match Grid::interpret_potential(potential) {
                 PotentialState::Several(count) => {
                     if count < result_count {
@@ -115,28 +234,50 @@ impl Grid {
         return Option::Some((result_index, result_potential, result_count));
     }
 
-    fn solve(&mut self) {
+    fn solve(&mut self) -> SolveStats {
         self.compute_potentials();
-        let (success, _) = self.try_solve_by_constrains();
+        let (success, naked_singles) = self.try_solve_by_constrains();
 
         if !success {
             panic!("Base constrains are wrong. Check your definition.");
         }
 
-        self.try_solve_recursive();
+        let mut stats = SolveStats { naked_singles, ..Default::default() };
+        let mut solutions = Vec::new();
+        self.try_solve_recursive(&mut solutions, 1, &mut stats, 0);
+
+        return stats;
+    }
+
+    fn count_solutions(&mut self, limit: usize) -> usize {
+        self.compute_potentials();
+        let (success, _) = self.try_solve_by_constrains();
+
+        if !success {
+            return 0;
+        }
+
+        let mut stats = SolveStats::default();
+        let mut solutions = Vec::new();
+        self.try_solve_recursive(&mut solutions, limit, &mut stats, 0);
+
+        return solutions.len();
     }
 
     fn compute_potentials(&mut self) {
         for potential in self.lines_state.iter_mut() { *potential = 0x3FE; }
         for potential in self.columns_state.iter_mut() { *potential = 0x3FE; }
+        for potential in self.boxes_state.iter_mut() { *potential = 0x3FE; }
 
         for (index, value) in self.cells.iter().enumerate() {
             if *value > 0 {
                 let value_bit = 0x3FE & (1 << value);
                 let (line, column) = Grid::coordinates_of(index);
+                let box_index = Grid::box_index_of(line, column);
 
                 self.lines_state[line] &= !value_bit;
                 self.columns_state[column] &= !value_bit;
+                self.boxes_state[box_index] &= !value_bit;
             }
         }
     }
@@ -155,8 +296,9 @@ impl Grid {
                 }
 
                 let (line, column) = Grid::coordinates_of(index);
+                let box_index = Grid::box_index_of(line, column);
 
-                match Grid::interpret_potential(self.lines_state[line] & self.columns_state[column]) {
+                match Grid::interpret_potential(self.lines_state[line] & self.columns_state[column] & self.boxes_state[box_index]) {
                     PotentialState::One(value) => {
                         // This value can be solved!
                         *cell = value;
@@ -187,19 +329,24 @@ impl Grid {
         }
     }
 
-    fn try_solve_recursive(&mut self) -> bool {
+    fn try_solve_recursive(&mut self, solutions: &mut Vec<[u8; 9 * 9]>, limit: usize, stats: &mut SolveStats, depth: usize) -> bool {
+        stats.max_depth = stats.max_depth.max(depth);
+
         if !self.any_unsolved_cell() {
-            return true;
+            solutions.push(self.cells);
+            return solutions.len() >= limit;
         }
 
         if let Some((cell_index, potential, potential_count)) = self.try_find_cell_with_low_potential() {
             for potential_index in 0..potential_count {
                 self.cells[cell_index] = Grid::get_potential_value_at(potential, potential_index);
                 self.compute_potentials();
+                stats.guesses += 1;
 
                 let (success, number_of_solved_indices) = self.try_solve_by_constrains();
+                stats.naked_singles += number_of_solved_indices;
 
-                if success && self.try_solve_recursive() {
+                if success && self.try_solve_recursive(solutions, limit, stats, depth + 1) {
                     return true;
                 }
 
@@ -213,6 +360,35 @@ impl Grid {
     }
 }
 
+impl FromStr for Grid {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Grid, ParseError> {
+        let trimmed = input.trim();
+        if trimmed.chars().count() != 9 * 9 {
+            return Err(ParseError::InvalidLength(trimmed.chars().count()));
+        }
+
+        let mut values: Vec<(u8, (usize, usize))> = Vec::new();
+        for (index, character) in trimmed.chars().enumerate() {
+            let value = match character {
+                '1'..='9' => character.to_digit(10).unwrap() as u8,
+                '0' | '.' => 0,
+                _ => return Err(ParseError::InvalidDigit(character)),
+            };
+
+            if value > 0 {
+                values.push((value, Grid::coordinates_of(index)));
+            }
+        }
+
+        let grid = Grid::create(&values);
+        grid.validate_givens()?;
+
+        return Ok(grid);
+    }
+}
+
 impl fmt::Display for Grid {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for line in 0..9 {
@@ -232,7 +408,49 @@ impl fmt::Display for Grid {
     }
 }
 
+fn grid_from_stdin() -> Result<Option<Grid>, ParseError> {
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)
+        .map_err(|error| ParseError::InvalidFormat(error.to_string()))?;
+
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+
+    if trimmed.starts_with("9,9") {
+        return Grid::from_reader(trimmed.as_bytes()).map(Some);
+    }
+
+    return trimmed.parse::<Grid>().map(Some);
+}
+
 fn main() {
+    match grid_from_stdin() {
+        Ok(Some(mut piped_grid)) => {
+            println!("{}", piped_grid);
+
+            let solution_count = piped_grid.clone().count_solutions(2);
+            match solution_count {
+                0 => println!("This puzzle has no solution."),
+                1 => println!("This puzzle has exactly one solution."),
+                _ => println!("This puzzle has multiple solutions."),
+            }
+
+            let stats = piped_grid.solve();
+            println!("{}", piped_grid);
+            println!("{:?}", stats);
+            return;
+        },
+
+        Ok(None) => {},
+
+        Err(error) => {
+            eprintln!("Could not parse puzzle from stdin: {}", error);
+            std::process::exit(1);
+        },
+    }
+
     let init : [(u8, (usize, usize)); 32] = [
         (3, (0, 0)),
         (6, (0, 2)),
@@ -270,6 +488,19 @@ fn main() {
 
     let mut my_grid = Grid::create(&init);
     println!("{}", my_grid);
-    my_grid.solve();
+
+    let solution_count = Grid::create(&init).count_solutions(2);
+    match solution_count {
+        0 => println!("This puzzle has no solution."),
+        1 => println!("This puzzle has exactly one solution."),
+        _ => println!("This puzzle has multiple solutions."),
+    }
+
+    let initial_solution_rate = my_grid.solution_rate();
+    let stats = my_grid.solve();
     println!("{}", my_grid);
+    println!(
+        "starting solution rate: {:.2}, naked singles: {}, guesses: {}, max depth: {}",
+        initial_solution_rate, stats.naked_singles, stats.guesses, stats.max_depth,
+    );
 }